@@ -0,0 +1,2 @@
+pub mod utill;
+pub mod wallet;
@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+/// Base directory for taker-side state; `CbfBlockchain` keeps its chain data
+/// under `cbf/` here unless given another data directory. Resolves to
+/// `~/.coinswap/taker`, or to `./.coinswap/taker` if `HOME` isn't set.
+pub fn get_taker_dir() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    home.join(".coinswap").join("taker")
+}
@@ -1,17 +1,22 @@
 use std::{
     cell::{Cell, RefCell},
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
     net::SocketAddr,
     path::PathBuf,
     thread,
     time::Duration,
 };
 
-use bitcoin::{absolute::Height, OutPoint, Script};
-use log::{debug, info};
+use bitcoin::{absolute::Height, FeeRate, OutPoint, Script, Txid};
+use log::{debug, info, warn};
 use nakamoto::{
     chain::Transaction,
-    client::{chan::Receiver, Client, Config, Event, Handle as ClientHandle, handle::Handle},
+    client::{
+        chan::{self, Receiver, Sender},
+        Client, Config, Event, Handle as ClientHandle,
+        handle::Handle,
+    },
     net::poll::Waker,
     p2p::fsm::fees::FeeEstimate,
 };
@@ -23,16 +28,93 @@ use crate::{
 
 type Reactor = nakamoto::net::poll::Reactor<std::net::TcpStream>;
 
+/// Lifecycle of a transaction we submitted to the network, mirroring the
+/// coarse states the taker needs to poll for before moving a swap on:
+/// has it left our node, has a peer seen it in their mempool, has it
+/// confirmed, or did it fall off the mempool without confirming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    Unconfirmed,
+    InMempool,
+    Confirmed(u32),
+    Stale,
+}
+
+struct BroadcastEntry {
+    tx: Transaction,
+    submitted_height: u32,
+    status: TxStatus,
+    // Height at which `status` became `Confirmed`/`Stale`, used to age out
+    // settled entries instead of evicting them the instant they settle.
+    settled_at: Option<u32>,
+}
+
+/// Keep a settled (confirmed/stale) broadcast entry around for this many
+/// blocks after it settles, so `tx_status` has a window to observe the
+/// terminal state before it's pruned.
+const BROADCAST_RETENTION_BLOCKS: u32 = 144;
+
+/// Tracks how deep a transaction is buried so callers can be told once it
+/// has reached their required finality depth, matching the
+/// finality-confirmation model swap wallets use before advancing HTLC /
+/// contract states.
+struct FinalityWatch {
+    confirm_height: Option<u32>,
+    required_confirmations: u32,
+}
+
+/// Rebroadcast a still-unconfirmed tx after this many blocks have passed
+/// since it was last submitted.
+const DEFAULT_REBROADCAST_AFTER_BLOCKS: u32 = 6;
+
+/// Default BIP32 "stop gap" of unused look-ahead addresses to keep
+/// registered for BIP158 filter matching.
+const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Cap on how many blocks of fee samples we keep, so `fee_data` doesn't grow
+/// unbounded over a long-running sync.
+const MAX_FEE_SAMPLES: usize = 144;
+
 pub struct CbfBlockchain {
     receiver: Receiver<Event>,
     client_handle: ClientHandle<Waker>,
     timeout: Duration,
-    fee_data: Cell<HashMap<u32, FeeEstimate>>,
-    broadcasted_txs: Cell<Vec<Transaction>>,
+    // Per-block fee samples, most recent `MAX_FEE_SAMPLES` heights only.
+    fee_data: Cell<BTreeMap<u32, FeeEstimate>>,
+    broadcasted_txs: Cell<HashMap<Txid, BroadcastEntry>>,
+    rebroadcast_after_blocks: u32,
+    finality_watches: Cell<HashMap<Txid, FinalityWatch>>,
     last_sync_height: Cell<u32>,
+    // Txids applied to the wallet at each height, ordered so a reorg can be
+    // unwound newest-block-first without rescanning from genesis.
+    applied_txids: Cell<BTreeMap<u32, Vec<Txid>>>,
+    // Txids disconnected during an in-flight reorg, staged for a deferred
+    // revert rather than reverted immediately, keyed by the height they
+    // were applied at and preserving the order they were applied in
+    // within that height (so a tx spending another tx's output from the
+    // same block unwinds in reverse-application order, not arbitrary
+    // order). If the reconnected side's `BlockMatched` reports the same
+    // txid again, `cancel_pending_revert` strikes it out here instead of
+    // it ever being un-applied — this is what mirrors Nakamoto's
+    // reverted-vs-connected header diff and keeps a tx present on both
+    // sides of the fork from being double-removed and reapplied.
+    pending_reverts: Cell<BTreeMap<u32, Vec<Txid>>>,
+    // Fork point (inclusive lower bound) of an in-flight reorg: set on the
+    // first `BlockDisconnected` of a disconnect sequence and held at the
+    // lowest fork point seen if more blocks are disconnected before we
+    // reconnect. Cleared once `Synced` reports a height at or past it, i.e.
+    // once the reconnected side has actually been re-scanned.
+    reorg_fork_height: Cell<Option<u32>>,
+    // Woken up by `stop()` so `process_events` can return instead of
+    // blocking on `receiver.recv()` forever.
+    stop_tx: Sender<()>,
+    stop_rx: Receiver<()>,
+    thread_handle: RefCell<Option<thread::JoinHandle<Result<(), nakamoto::client::Error>>>>,
+    gap_limit: u32,
     wallet: Wallet,
 }
 
+#[derive(Debug)]
 pub enum CbfSyncError {
     NakamotoError(nakamoto::client::Error),
     WalletError(crate::wallet::error::WalletError),
@@ -55,6 +137,7 @@ impl CbfBlockchain {
         network: bitcoin::Network,
         datadir: Option<PathBuf>,
         peers: Vec<SocketAddr>,
+        gap_limit: Option<u32>,
         wallet: Wallet,
     ) -> Result<Self, CbfSyncError> {
         let root = if let Some(dir) = datadir {
@@ -71,9 +154,9 @@ impl CbfBlockchain {
         };
 
         let client_handle = cbf_client.handle();
-        thread::spawn(move || {
-            cbf_client.run(client_cfg).unwrap();
-        });
+        let receiver = cbf_client.events();
+        let thread_handle = thread::spawn(move || cbf_client.run(client_cfg));
+        let (stop_tx, stop_rx) = chan::bounded(1);
         for peer in peers {
             client_handle
                 .connect(peer)
@@ -85,20 +168,64 @@ impl CbfBlockchain {
             receiver,
             client_handle,
             timeout: Duration::from_secs(60), // This is nakamoto default client timeout
-            fee_data: Cell::new(HashMap::new()),
-            broadcasted_txs: Cell::new(Vec::new()),
+            fee_data: Cell::new(BTreeMap::new()),
+            broadcasted_txs: Cell::new(HashMap::new()),
+            rebroadcast_after_blocks: DEFAULT_REBROADCAST_AFTER_BLOCKS,
+            finality_watches: Cell::new(HashMap::new()),
             last_sync_height: Cell::new(0u32),
+            applied_txids: Cell::new(BTreeMap::new()),
+            pending_reverts: Cell::new(BTreeMap::new()),
+            reorg_fork_height: Cell::new(None),
+            stop_tx,
+            stop_rx,
+            thread_handle: RefCell::new(Some(thread_handle)),
+            gap_limit: gap_limit.unwrap_or(DEFAULT_GAP_LIMIT),
             wallet,
         })
     }
 
+    /// Signal the nakamoto client to stop and wake the reactor, unblock
+    /// `process_events`, then join the client thread so an error returned by
+    /// the client's run loop surfaces to the caller instead of leaking the
+    /// thread. A panic in the client thread is logged, not returned.
+    pub fn stop(&self) -> Result<(), CbfSyncError> {
+        self.client_handle
+            .shutdown()
+            .map_err(nakamoto::client::Error::from)?;
+        let _ = self.stop_tx.send(());
+
+        if let Some(handle) = self.thread_handle.borrow_mut().take() {
+            match handle.join() {
+                Ok(result) => result.map_err(CbfSyncError::from)?,
+                Err(_) => warn!("CBF client thread panicked"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resume from the persisted sync height and script registry instead of
+    /// always starting a rescan at height 0. If nothing was persisted yet
+    /// (first run), derive the initial gap-limit window of watch scripts
+    /// from the wallet's descriptors.
     pub fn initialize_cbf_sync(&mut self) -> Result<(), CbfSyncError> {
-        let last_sync_height = self
+        let (chain_tip, _) = self
             .client_handle
             .get_tip()
-            .map_err(nakamoto::client::Error::from)?;
-        let (height, _) = last_sync_height?;
-        self.last_sync_height.set(height);
+            .map_err(nakamoto::client::Error::from)??;
+
+        let resume_height = self.wallet.persisted_sync_height()?.unwrap_or(0).min(chain_tip);
+        self.last_sync_height.set(resume_height);
+
+        let tracked = self.wallet.tracked_scripts()?;
+        let scripts = if tracked.is_empty() {
+            self.wallet.derive_gap_limit_scripts(self.gap_limit)?
+        } else {
+            tracked
+        };
+        self.register_scripts(&scripts)?;
+        self.scan(resume_height, scripts);
+
         Ok(())
     }
 
@@ -108,12 +235,195 @@ impl CbfBlockchain {
             .rescan((from as u64).., scripts.into_iter());
     }
 
+    /// Persist newly derived scripts to the wallet's script registry so it
+    /// survives restarts.
+    fn register_scripts(&self, scripts: &[Script]) -> Result<(), CbfSyncError> {
+        for script in scripts {
+            self.wallet.track_script(script.clone())?;
+        }
+        Ok(())
+    }
+
+    /// After an address is used, derive enough fresh look-ahead addresses to
+    /// keep a full gap-limit window of unused scripts registered, and issue
+    /// a follow-up rescan covering just the newly added ones.
+    fn maintain_gap_limit(&mut self, used_script: &Script) -> Result<(), CbfSyncError> {
+        let fresh_scripts = self
+            .wallet
+            .extend_derivation_for_gap_limit(used_script, self.gap_limit)?;
+
+        if !fresh_scripts.is_empty() {
+            self.register_scripts(&fresh_scripts)?;
+            self.scan(self.last_sync_height.get(), fresh_scripts);
+        }
+
+        Ok(())
+    }
+
     fn add_fee_data(&self, height: u32, fee_estimate: FeeEstimate) {
         let mut data = self.fee_data.take();
         data.insert(height, fee_estimate);
+        while data.len() > MAX_FEE_SAMPLES {
+            let oldest = *data.keys().next().expect("data is non-empty");
+            data.remove(&oldest);
+        }
         self.fee_data.set(data);
     }
 
+    /// Map a desired confirmation target (in blocks, e.g. 1/3/6/24 as used by
+    /// LDK's `ConfirmationTarget` tiers) onto the accumulated per-block fee
+    /// samples, smoothing over a short window so a single noisy block
+    /// doesn't dominate. Falls back to a minimum relay feerate when there
+    /// aren't enough samples yet.
+    pub fn estimate_feerate(&self, target_blocks: usize) -> Option<FeeRate> {
+        let data = self.fee_data.take();
+        let window = Self::sample_window(target_blocks);
+        let mut samples: Vec<f64> = data
+            .values()
+            .rev()
+            .take(window)
+            .map(|estimate| Self::bucket_for_target(estimate, target_blocks))
+            .collect();
+        self.fee_data.set(data);
+
+        if samples.is_empty() {
+            return Some(Self::min_relay_feerate());
+        }
+
+        // Chain-derived fee samples aren't locally controlled — an empty
+        // block can legitimately produce a 0/0 average and surface as NaN,
+        // so fall back to treating non-finite samples as equal rather than
+        // panicking the sync thread on `expect`.
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        let smoothed = samples[samples.len() / 2];
+
+        FeeRate::from_sat_per_vb(smoothed.round() as u64).or(Some(Self::min_relay_feerate()))
+    }
+
+    fn bucket_for_target(estimate: &FeeEstimate, target_blocks: usize) -> f64 {
+        match target_blocks {
+            0..=2 => estimate.high,
+            3..=6 => estimate.medium,
+            _ => estimate.low,
+        }
+    }
+
+    fn sample_window(target_blocks: usize) -> usize {
+        (target_blocks.max(1) * 2).min(MAX_FEE_SAMPLES)
+    }
+
+    fn min_relay_feerate() -> FeeRate {
+        FeeRate::from_sat_per_vb(1).expect("1 sat/vB is a valid feerate")
+    }
+
+    /// Submit a wallet transaction to the network and start tracking it so
+    /// `tx_status` can later tell swap logic whether a funding or timelock
+    /// transaction has propagated.
+    pub fn broadcast_transaction(&self, tx: Transaction) -> Result<Txid, CbfSyncError> {
+        let txid = tx.txid();
+        self.client_handle
+            .submit_transaction(tx.clone())
+            .map_err(nakamoto::client::Error::from)?;
+
+        let mut broadcasts = self.broadcasted_txs.take();
+        broadcasts.insert(
+            txid,
+            BroadcastEntry {
+                tx,
+                submitted_height: self.last_sync_height.get(),
+                status: TxStatus::Unconfirmed,
+                settled_at: None,
+            },
+        );
+        self.broadcasted_txs.set(broadcasts);
+
+        Ok(txid)
+    }
+
+    pub fn tx_status(&self, txid: &Txid) -> Option<TxStatus> {
+        let broadcasts = self.broadcasted_txs.take();
+        let status = broadcasts.get(txid).map(|entry| entry.status);
+        self.broadcasted_txs.set(broadcasts);
+        status
+    }
+
+    /// Let a caller that has already acted on a terminal status (e.g.
+    /// advanced a swap past a confirmed funding tx) drop it from tracking
+    /// right away instead of waiting for the age-based prune.
+    pub fn acknowledge_tx_status(&self, txid: &Txid) {
+        let mut broadcasts = self.broadcasted_txs.take();
+        broadcasts.remove(txid);
+        self.broadcasted_txs.set(broadcasts);
+    }
+
+    fn update_tx_status(&self, txid: Txid, status: nakamoto::client::TxStatus) {
+        let mut broadcasts = self.broadcasted_txs.take();
+        let mut confirmed_at = None;
+        if let Some(entry) = broadcasts.get_mut(&txid) {
+            entry.status = match status {
+                nakamoto::client::TxStatus::Acknowledged { .. } => TxStatus::InMempool,
+                nakamoto::client::TxStatus::Confirmed { height, .. } => {
+                    confirmed_at = Some(height);
+                    entry.settled_at = Some(height);
+                    TxStatus::Confirmed(height)
+                }
+                nakamoto::client::TxStatus::Stale { .. } | nakamoto::client::TxStatus::Invalid { .. } => {
+                    entry.settled_at = Some(self.last_sync_height.get());
+                    TxStatus::Stale
+                }
+                _ => entry.status,
+            };
+        }
+        self.broadcasted_txs.set(broadcasts);
+
+        if let Some(height) = confirmed_at {
+            self.mark_confirmed(txid, height);
+        }
+    }
+
+    /// Drop broadcast entries that settled (confirmed/stale)
+    /// `BROADCAST_RETENTION_BLOCKS` or more blocks ago. Entries still in flight
+    /// (`Unconfirmed`/`InMempool`) are never pruned here.
+    fn prune_settled_broadcasts(&self, current_height: u32) {
+        let mut broadcasts = self.broadcasted_txs.take();
+        broadcasts.retain(|_, entry| match entry.settled_at {
+            Some(settled_at) => {
+                current_height.saturating_sub(settled_at) < BROADCAST_RETENTION_BLOCKS
+            }
+            None => true,
+        });
+        self.broadcasted_txs.set(broadcasts);
+    }
+
+    /// Resubmit anything still `Unconfirmed` or merely `InMempool` after
+    /// `rebroadcast_after_blocks` have passed since it was (re)submitted —
+    /// an acknowledgement from one peer doesn't mean the tx survives in
+    /// every mempool, so acknowledged-but-unconfirmed txs must also be
+    /// retried.
+    fn rebroadcast_stale_txs(&self, current_height: u32) -> Result<(), CbfSyncError> {
+        let mut broadcasts = self.broadcasted_txs.take();
+        let mut due = Vec::new();
+
+        for entry in broadcasts.values_mut() {
+            if matches!(entry.status, TxStatus::Unconfirmed | TxStatus::InMempool)
+                && current_height.saturating_sub(entry.submitted_height) >= self.rebroadcast_after_blocks
+            {
+                entry.submitted_height = current_height;
+                due.push(entry.tx.clone());
+            }
+        }
+        self.broadcasted_txs.set(broadcasts);
+
+        for tx in due {
+            debug!("Rebroadcasting unconfirmed transaction: {}", tx.txid());
+            self.client_handle
+                .submit_transaction(tx)
+                .map_err(nakamoto::client::Error::from)?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_next_event(&self) -> Result<Event, CbfSyncError> {
         Ok(self
             .receiver
@@ -121,9 +431,37 @@ impl CbfBlockchain {
             .map_err(|e| nakamoto::client::Error::from(nakamoto::client::handle::Error::from(e)))?)
     }
 
+    /// Block on either the next client event or a `stop()` signal, whichever
+    /// arrives first. Returns `None` once stopped so `process_events` can
+    /// return cleanly instead of blocking forever on `receiver.recv()`.
+    fn next_event_or_stop(&self) -> Result<Option<Event>, CbfSyncError> {
+        let mut select = chan::Select::new();
+        let event_op = select.recv(&self.receiver);
+        let stop_op = select.recv(&self.stop_rx);
+        let selected = select.select();
+
+        match selected.index() {
+            i if i == stop_op => {
+                let _ = selected.recv(&self.stop_rx);
+                Ok(None)
+            }
+            i if i == event_op => {
+                let event = selected
+                    .recv(&self.receiver)
+                    .map_err(|e| nakamoto::client::Error::from(nakamoto::client::handle::Error::from(e)))?;
+                Ok(Some(event))
+            }
+            _ => unreachable!("Select only registered two operations"),
+        }
+    }
+
     pub fn process_events(&mut self) -> Result<(), CbfSyncError> {
         loop {
-            match self.get_next_event()? {
+            let Some(event) = self.next_event_or_stop()? else {
+                info!("CBF sync stopped");
+                return Ok(());
+            };
+            match event {
                 Event::Ready { tip, filter_tip } => {
                     info!("CBF sync ready. Tip: {}, Filter tip: {}", tip, filter_tip);
                 }
@@ -144,28 +482,90 @@ impl CbfBlockchain {
                 }
                 Event::BlockConnected { hash, height, .. } => {
                     info!("Block connected: {} at height {}", hash, height);
+                    self.last_sync_height.set(height);
+                    self.rebroadcast_stale_txs(height)?;
+                    self.prune_settled_broadcasts(height);
                 }
                 Event::BlockDisconnected { hash, height, .. } => {
                     info!("Block disconnected: {} at height {}", hash, height);
+                    self.stage_for_revert(height);
+                    // Every finality watch confirmed at or above the
+                    // disconnected height needs re-arming, not just the ones
+                    // tied to a wallet-relevant txid in `applied_txids` —
+                    // `watch_for_finality`/`watch_outgoing_swap_coin` can be
+                    // registered for a broadcast-only or external txid too.
+                    self.reset_finality_watches_from(height);
+                    // The fork point is the last block both chains share. Park
+                    // `last_sync_height` there instead of letting it run ahead,
+                    // so a crash mid-reorg resumes the rescan from a safe point
+                    // rather than skipping the blocks we just unwound.
+                    let fork_point = height.saturating_sub(1);
+                    if self.last_sync_height.get() > fork_point {
+                        self.last_sync_height.set(fork_point);
+                        self.wallet.persist_sync_height(fork_point)?;
+                    }
+                    // Hold the lowest fork point across a multi-block
+                    // disconnect sequence, so `Synced` only treats the
+                    // reorg as resolved once it reports a height at or
+                    // past the deepest point we diverged at.
+                    let pending_fork = self.reorg_fork_height.get();
+                    self.reorg_fork_height.set(Some(match pending_fork {
+                        Some(existing) => existing.min(fork_point),
+                        None => fork_point,
+                    }));
                 }
                 Event::BlockMatched { hash, header, height, transactions } => {
                     info!("Block matched: {} at height {}. Transactions: {}", hash, height, transactions.len());
                     for transaction in transactions {
-                        debug!("Processing transaction: {}", transaction.txid());
-                        self.process_transaction(transaction)?;
+                        let txid = transaction.txid();
+                        debug!("Processing transaction: {}", txid);
+                        // If this txid was staged for a revert by an
+                        // earlier disconnect, it just reappeared unchanged
+                        // on the reconnected side: cancel the revert rather
+                        // than unwinding state that `process_transaction`
+                        // below is about to reapply anyway.
+                        self.cancel_pending_revert(&txid);
+                        if self.is_watched(&txid) {
+                            self.mark_confirmed(txid, height);
+                        }
+                        self.process_transaction(height, transaction)?;
                     }
                 }
                 Event::FeeEstimated { block, height, fees } => {
                     debug!("Fee estimated for block: {} at height {}. Fees: {:?}", block, height, fees);
+                    self.add_fee_data(height, fees);
                 }
                 Event::FilterProcessed { block, height, matched, valid } => {
                     debug!("Filter processed for block: {} at height {}. Matched: {}, Valid: {}", block, height, matched, valid);
                 }
                 Event::TxStatusChanged { txid, status } => {
                     debug!("Transaction status changed: {}. Status: {:?}", txid, status);
+                    self.update_tx_status(txid, status);
                 }
                 Event::Synced { height, tip } => {
                     info!("Sync complete up to {}/{}", height, tip);
+                    self.last_sync_height.set(height);
+                    self.wallet.persist_sync_height(height)?;
+
+                    // Only treat the reorg as resolved, and only treat
+                    // `height == tip` as final, once `height` has actually
+                    // reached the fork point we diverged at — otherwise
+                    // we'd mark the fork "final" before `BlockMatched`
+                    // events for the reconnected side have had a chance to
+                    // arrive and re-credit the wallet. `fork_point` is the
+                    // last common/valid block, so a reorg that shrinks the
+                    // chain down to exactly that block must also count as
+                    // resolved, hence `>=` rather than `>`.
+                    let reorg_resolved = match self.reorg_fork_height.get() {
+                        Some(fork_point) => height >= fork_point,
+                        None => true,
+                    };
+                    if !reorg_resolved {
+                        continue;
+                    }
+                    self.finalize_pending_reverts()?;
+                    self.reorg_fork_height.set(None);
+
                     if height == tip {
                         break;
                     }
@@ -175,8 +575,11 @@ impl CbfBlockchain {
         Ok(())
     }
 
-    fn process_transaction(&mut self, transaction: Transaction) -> Result<(), CbfSyncError> {
-        let txid = transaction.txid();
+    fn process_transaction(
+        &mut self,
+        height: u32,
+        transaction: Transaction,
+    ) -> Result<(), CbfSyncError> {
         let output_scripts: Vec<Script> = transaction
             .output
             .iter()
@@ -192,12 +595,148 @@ impl CbfBlockchain {
         let relevant_inputs = self.find_relevant_inputs(&input_outpoints)?;
 
         if !relevant_inputs.is_empty() || !relevant_outputs.is_empty() {
-            self.update_wallet_with_tx(&transaction, &relevant_outputs, &relevant_inputs)?;
+            self.update_wallet_with_tx(height, &transaction, &relevant_outputs, &relevant_inputs)?;
+        }
+
+        Ok(())
+    }
+
+    /// Move every tx applied at `height` out of `applied_txids` and into
+    /// `pending_reverts` instead of reverting it immediately. A height with
+    /// nothing recorded (e.g. a disconnect for a block we never matched
+    /// anything in) is a no-op.
+    fn stage_for_revert(&mut self, height: u32) {
+        let mut applied = self.applied_txids.take();
+        let Some(txids) = applied.remove(&height) else {
+            self.applied_txids.set(applied);
+            return;
+        };
+        self.applied_txids.set(applied);
+
+        let mut pending = self.pending_reverts.take();
+        pending.insert(height, txids);
+        self.pending_reverts.set(pending);
+    }
+
+    /// A txid staged for revert just reappeared in a `BlockMatched` for the
+    /// reconnected side, so it's unchanged across the fork: drop it from
+    /// `pending_reverts` without ever calling `revert_transaction` on it.
+    fn cancel_pending_revert(&self, txid: &Txid) {
+        let mut pending = self.pending_reverts.take();
+        pending.retain(|_, txids| {
+            txids.retain(|staged| staged != txid);
+            !txids.is_empty()
+        });
+        self.pending_reverts.set(pending);
+    }
+
+    /// Once a reorg has resolved, revert everything still staged — these
+    /// are exactly the txids that were disconnected and never reappeared on
+    /// the reconnected side, so reverting them (and only them) is what
+    /// keeps a tx present on both sides of the fork from being
+    /// double-removed and reapplied. Heights are reverted newest-first, and
+    /// within a height txids are reverted in reverse-application order, so
+    /// a tx spending another tx's output in the same block is unwound
+    /// before the tx it spends from.
+    fn finalize_pending_reverts(&mut self) -> Result<(), CbfSyncError> {
+        let pending = self.pending_reverts.take();
+
+        for (_, txids) in pending.into_iter().rev() {
+            for txid in txids.into_iter().rev() {
+                self.wallet.revert_transaction(&txid)?;
+            }
         }
 
         Ok(())
     }
 
+    /// Register a txid for finality tracking: `is_final` will report `true`
+    /// once it has been buried under `finality_confirmations` blocks. The
+    /// watch is confirmed from `BlockMatched`, which BIP158 only delivers
+    /// for blocks that match one of our registered scripts — so a txid
+    /// with no wallet-tracked input or output (e.g. an external contract
+    /// tx we never broadcast and that pays none of our scripts) will never
+    /// confirm through this path. It still confirms normally if it was
+    /// submitted through `broadcast_transaction`, since `TxStatusChanged`
+    /// reports its confirmation independently of the filter match.
+    pub fn watch_for_finality(&self, txid: Txid, finality_confirmations: u32) {
+        let mut watches = self.finality_watches.take();
+        watches.insert(
+            txid,
+            FinalityWatch {
+                confirm_height: None,
+                required_confirmations: finality_confirmations,
+            },
+        );
+        self.finality_watches.set(watches);
+    }
+
+    /// Convenience wrapper for the common case of watching an outgoing swap
+    /// coin's funding/contract transaction for finality.
+    pub fn watch_outgoing_swap_coin(&self, coin: &OutgoingSwapCoin, finality_confirmations: u32) {
+        self.watch_for_finality(coin.contract_tx.txid(), finality_confirmations);
+    }
+
+    /// Let a caller that has already acted on `is_final` returning `true`
+    /// drop the watch from tracking, mirroring `acknowledge_tx_status` for
+    /// `broadcasted_txs` — otherwise a long-running taker leaks one
+    /// `FinalityWatch` entry per contract transaction forever.
+    pub fn unwatch_finality(&self, txid: &Txid) {
+        let mut watches = self.finality_watches.take();
+        watches.remove(txid);
+        self.finality_watches.set(watches);
+    }
+
+    pub fn is_final(&self, txid: &Txid) -> bool {
+        let watches = self.finality_watches.take();
+        let is_final = watches.get(txid).is_some_and(|watch| {
+            watch.confirm_height.is_some_and(|confirm_height| {
+                self.confirmation_depth(confirm_height) >= watch.required_confirmations
+            })
+        });
+        self.finality_watches.set(watches);
+        is_final
+    }
+
+    fn confirmation_depth(&self, confirm_height: u32) -> u32 {
+        self.last_sync_height
+            .get()
+            .saturating_sub(confirm_height)
+            .saturating_add(1)
+    }
+
+    /// Whether `txid` has a registered finality watch, regardless of
+    /// whether it's ever touched a wallet-tracked script.
+    fn is_watched(&self, txid: &Txid) -> bool {
+        let watches = self.finality_watches.take();
+        let watched = watches.contains_key(txid);
+        self.finality_watches.set(watches);
+        watched
+    }
+
+    fn mark_confirmed(&self, txid: Txid, height: u32) {
+        let mut watches = self.finality_watches.take();
+        if let Some(watch) = watches.get_mut(&txid) {
+            watch.confirm_height = Some(height);
+        }
+        self.finality_watches.set(watches);
+    }
+
+    /// A reorg that disconnects a confirming block un-confirms every watch
+    /// confirmed at or above that height, final or not — regardless of
+    /// whether the txid ever touched a wallet-tracked script — and re-arms
+    /// it so finality is only signalled once the tx confirms again on the
+    /// new chain.
+    fn reset_finality_watches_from(&self, disconnected_height: u32) {
+        let mut watches = self.finality_watches.take();
+        for watch in watches.values_mut() {
+            if watch.confirm_height.is_some_and(|h| h >= disconnected_height) {
+                watch.confirm_height = None;
+            }
+        }
+        self.finality_watches.set(watches);
+    }
+
     fn find_relevant_outputs(
         &self,
         output_scripts: &[Script],
@@ -213,8 +752,21 @@ impl CbfBlockchain {
         Ok(relevant_outputs)
     }
 
+    fn find_relevant_inputs(&self, input_outpoints: &[OutPoint]) -> Result<Vec<OutPoint>, CbfSyncError> {
+        let mut relevant_inputs = Vec::new();
+
+        for outpoint in input_outpoints {
+            if self.wallet.is_utxo_tracked(outpoint)? {
+                relevant_inputs.push(*outpoint);
+            }
+        }
+
+        Ok(relevant_inputs)
+    }
+
     fn update_wallet_with_tx(
         &mut self,
+        height: u32,
         transaction: &Transaction,
         relevant_outputs: &[(u32, Script)],
         relevant_inputs: &[OutPoint],
@@ -224,6 +776,7 @@ impl CbfBlockchain {
         for (vout, script) in relevant_outputs {
             let amount = transaction.output[*vout as usize].value;
             self.wallet.add_utxo(OutPoint { txid, vout: *vout }, amount, script.clone())?;
+            self.maintain_gap_limit(script)?;
         }
 
         for outpoint in relevant_inputs {
@@ -231,8 +784,41 @@ impl CbfBlockchain {
         }
 
         self.wallet.store_transaction(transaction.clone())?;
-        // functions store_transaction, remove_utxo,add_utxo, is_script_tracked, is_utxo_tracked needs to be added.
-        // And we also need to define the methods to add scripts to track and then get them back.
+
+        let mut applied = self.applied_txids.take();
+        applied.entry(height).or_insert_with(Vec::new).push(txid);
+        self.applied_txids.set(applied);
+        self.mark_confirmed(txid, height);
+
         Ok(())
     }
 }
+
+impl Drop for CbfBlockchain {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop() {
+            warn!("Error stopping CBF client during drop: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_window_scales_with_target_and_caps_at_max_fee_samples() {
+        assert_eq!(CbfBlockchain::sample_window(0), 2);
+        assert_eq!(CbfBlockchain::sample_window(3), 6);
+        assert_eq!(CbfBlockchain::sample_window(1_000), MAX_FEE_SAMPLES);
+    }
+
+    #[test]
+    fn bucket_for_target_picks_the_tier_matching_confirmation_target() {
+        let estimate = FeeEstimate { high: 20.0, medium: 10.0, low: 2.0 };
+
+        assert_eq!(CbfBlockchain::bucket_for_target(&estimate, 1), 20.0);
+        assert_eq!(CbfBlockchain::bucket_for_target(&estimate, 6), 10.0);
+        assert_eq!(CbfBlockchain::bucket_for_target(&estimate, 24), 2.0);
+    }
+}
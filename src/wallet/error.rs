@@ -0,0 +1,26 @@
+use std::fmt;
+
+/// Errors surfaced by wallet operations invoked from the CBF sync loop —
+/// UTXO/script bookkeeping, persistence to disk, and descriptor derivation.
+#[derive(Debug)]
+pub enum WalletError {
+    Io(std::io::Error),
+    Derivation(String),
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletError::Io(err) => write!(f, "wallet I/O error: {}", err),
+            WalletError::Derivation(msg) => write!(f, "descriptor derivation error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+impl From<std::io::Error> for WalletError {
+    fn from(err: std::io::Error) -> Self {
+        WalletError::Io(err)
+    }
+}
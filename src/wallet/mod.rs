@@ -0,0 +1,361 @@
+pub mod cbf;
+pub mod error;
+
+pub use cbf::{CbfBlockchain, CbfSyncError, TxStatus};
+pub use error::WalletError;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs,
+    io::Write,
+    path::PathBuf,
+};
+
+use bitcoin::{
+    secp256k1::Secp256k1,
+    util::bip32::{ChildNumber, ExtendedPubKey},
+    Address, Amount, Network, OutPoint, PublicKey, Script, Transaction, Txid,
+};
+
+/// An outgoing swap coin: the contract transaction locking up the taker's
+/// funds for the duration of a coinswap, together with enough data to
+/// reclaim or redeem it once it's final.
+pub struct OutgoingSwapCoin {
+    pub contract_tx: Transaction,
+    pub contract_redeemscript: Script,
+    pub timelock_pubkey: PublicKey,
+}
+
+struct Utxo {
+    amount: Amount,
+    script_pubkey: Script,
+}
+
+struct StoredTx {
+    tx: Transaction,
+    confirmed: bool,
+}
+
+/// A minimal single-descriptor wallet: a UTXO set, a tx store, a BIP158
+/// script registry, and gap-limit address derivation off one extended
+/// pubkey. The derivation index and the last sync height are persisted
+/// under `data_dir` as plain counter files rather than a database; the
+/// UTXO set and the tx store are held in memory only and start empty on
+/// each `Wallet::new`.
+pub struct Wallet {
+    xpub: ExtendedPubKey,
+    network: Network,
+    data_dir: PathBuf,
+
+    utxos: RefCell<HashMap<OutPoint, Utxo>>,
+    // UTXOs removed by `remove_utxo`, kept around so `revert_transaction`
+    // can re-credit them if the spending tx is later reorged out.
+    spent_utxos: RefCell<HashMap<OutPoint, Utxo>>,
+    // Outpoints each tx created, so `revert_transaction` knows what to
+    // delete without re-deriving relevance from scratch.
+    created_by_tx: RefCell<HashMap<Txid, Vec<OutPoint>>>,
+    transactions: RefCell<HashMap<Txid, StoredTx>>,
+
+    tracked_scripts: RefCell<HashSet<Script>>,
+    derived_scripts: RefCell<Vec<Script>>,
+    next_derivation_index: RefCell<u32>,
+}
+
+impl Wallet {
+    pub fn new(xpub: ExtendedPubKey, network: Network, data_dir: PathBuf) -> Result<Self, WalletError> {
+        fs::create_dir_all(&data_dir)?;
+
+        let wallet = Self {
+            xpub,
+            network,
+            data_dir,
+            utxos: RefCell::new(HashMap::new()),
+            spent_utxos: RefCell::new(HashMap::new()),
+            created_by_tx: RefCell::new(HashMap::new()),
+            transactions: RefCell::new(HashMap::new()),
+            tracked_scripts: RefCell::new(HashSet::new()),
+            derived_scripts: RefCell::new(Vec::new()),
+            next_derivation_index: RefCell::new(0),
+        };
+
+        // Scripts aren't persisted individually: they're a pure function of
+        // the descriptor and an index, so re-deriving up to the persisted
+        // index is simpler than keeping a separate script file in sync.
+        let persisted_index = wallet.read_u32_file("derivation_index")?.unwrap_or(0);
+        for index in 0..persisted_index {
+            let script = wallet.derive_script_at(index)?;
+            wallet.tracked_scripts.borrow_mut().insert(script.clone());
+            wallet.derived_scripts.borrow_mut().push(script);
+        }
+        *wallet.next_derivation_index.borrow_mut() = persisted_index;
+
+        Ok(wallet)
+    }
+
+    // --- UTXO / transaction bookkeeping, called from the CBF sync loop ---
+
+    pub fn add_utxo(&self, outpoint: OutPoint, amount: Amount, script_pubkey: Script) -> Result<(), WalletError> {
+        self.utxos.borrow_mut().insert(outpoint, Utxo { amount, script_pubkey });
+        self.created_by_tx
+            .borrow_mut()
+            .entry(outpoint.txid)
+            .or_default()
+            .push(outpoint);
+        Ok(())
+    }
+
+    pub fn remove_utxo(&self, outpoint: &OutPoint) -> Result<(), WalletError> {
+        if let Some(utxo) = self.utxos.borrow_mut().remove(outpoint) {
+            self.spent_utxos.borrow_mut().insert(*outpoint, utxo);
+        }
+        Ok(())
+    }
+
+    pub fn store_transaction(&self, transaction: Transaction) -> Result<(), WalletError> {
+        self.transactions
+            .borrow_mut()
+            .insert(transaction.txid(), StoredTx { tx: transaction, confirmed: true });
+        Ok(())
+    }
+
+    pub fn is_script_tracked(&self, script: &Script) -> Result<bool, WalletError> {
+        Ok(self.tracked_scripts.borrow().contains(script))
+    }
+
+    pub fn is_utxo_tracked(&self, outpoint: &OutPoint) -> Result<bool, WalletError> {
+        Ok(self.utxos.borrow().contains_key(outpoint))
+    }
+
+    /// Whether `txid` is currently marked confirmed — `false` once
+    /// `revert_transaction` has unwound it, until it's re-applied.
+    pub fn is_confirmed(&self, txid: &Txid) -> Result<Option<bool>, WalletError> {
+        Ok(self.transactions.borrow().get(txid).map(|stored| stored.confirmed))
+    }
+
+    /// Un-apply everything `txid` did to the wallet: delete the UTXOs it
+    /// created, re-credit the UTXOs it spent from the `spent_utxos` journal
+    /// `remove_utxo` populated, and mark it unconfirmed rather than
+    /// forgetting it outright.
+    pub fn revert_transaction(&self, txid: &Txid) -> Result<(), WalletError> {
+        if let Some(created) = self.created_by_tx.borrow_mut().remove(txid) {
+            let mut utxos = self.utxos.borrow_mut();
+            for outpoint in created {
+                utxos.remove(&outpoint);
+            }
+        }
+
+        if let Some(stored) = self.transactions.borrow().get(txid) {
+            let mut utxos = self.utxos.borrow_mut();
+            let mut spent = self.spent_utxos.borrow_mut();
+            for input in &stored.tx.input {
+                if let Some(utxo) = spent.remove(&input.previous_output) {
+                    utxos.insert(input.previous_output, utxo);
+                }
+            }
+        }
+
+        if let Some(stored) = self.transactions.borrow_mut().get_mut(txid) {
+            stored.confirmed = false;
+        }
+
+        Ok(())
+    }
+
+    // --- sync height persistence ---
+
+    pub fn persisted_sync_height(&self) -> Result<Option<u32>, WalletError> {
+        self.read_u32_file("last_sync_height")
+    }
+
+    pub fn persist_sync_height(&self, height: u32) -> Result<(), WalletError> {
+        self.write_u32_file("last_sync_height", height)
+    }
+
+    // --- script registry ---
+
+    pub fn tracked_scripts(&self) -> Result<Vec<Script>, WalletError> {
+        Ok(self.tracked_scripts.borrow().iter().cloned().collect())
+    }
+
+    pub fn track_script(&self, script: Script) -> Result<(), WalletError> {
+        self.tracked_scripts.borrow_mut().insert(script);
+        Ok(())
+    }
+
+    // --- gap-limit descriptor derivation ---
+
+    pub fn derive_gap_limit_scripts(&self, gap_limit: u32) -> Result<Vec<Script>, WalletError> {
+        let mut scripts = Vec::with_capacity(gap_limit as usize);
+        for index in 0..gap_limit {
+            scripts.push(self.derive_script_at(index)?);
+        }
+
+        self.tracked_scripts.borrow_mut().extend(scripts.iter().cloned());
+        *self.derived_scripts.borrow_mut() = scripts.clone();
+        *self.next_derivation_index.borrow_mut() = gap_limit;
+        self.write_u32_file("derivation_index", gap_limit)?;
+
+        Ok(scripts)
+    }
+
+    pub fn extend_derivation_for_gap_limit(
+        &self,
+        used_script: &Script,
+        gap_limit: u32,
+    ) -> Result<Vec<Script>, WalletError> {
+        let used_index = match self.derived_scripts.borrow().iter().position(|s| s == used_script) {
+            Some(index) => index as u32,
+            None => return Ok(Vec::new()),
+        };
+
+        let next_index = *self.next_derivation_index.borrow();
+        let required_highest = used_index + gap_limit;
+        if required_highest < next_index {
+            return Ok(Vec::new());
+        }
+
+        let mut fresh = Vec::new();
+        for index in next_index..=required_highest {
+            let script = self.derive_script_at(index)?;
+            self.tracked_scripts.borrow_mut().insert(script.clone());
+            self.derived_scripts.borrow_mut().push(script.clone());
+            fresh.push(script);
+        }
+
+        *self.next_derivation_index.borrow_mut() = required_highest + 1;
+        self.write_u32_file("derivation_index", required_highest + 1)?;
+
+        Ok(fresh)
+    }
+
+    fn derive_script_at(&self, index: u32) -> Result<Script, WalletError> {
+        let secp = Secp256k1::verification_only();
+        let child_number = ChildNumber::from_normal_idx(index)
+            .map_err(|err| WalletError::Derivation(err.to_string()))?;
+        let child_xpub = self
+            .xpub
+            .derive_pub(&secp, &[child_number])
+            .map_err(|err| WalletError::Derivation(err.to_string()))?;
+        let pubkey = PublicKey::new(child_xpub.public_key);
+        let address = Address::p2wpkh(&pubkey, self.network)
+            .map_err(|err| WalletError::Derivation(err.to_string()))?;
+        Ok(address.script_pubkey())
+    }
+
+    // --- tiny file-backed counter persistence ---
+
+    fn read_u32_file(&self, name: &str) -> Result<Option<u32>, WalletError> {
+        match fs::read_to_string(self.data_dir.join(name)) {
+            Ok(contents) => Ok(contents.trim().parse::<u32>().ok()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(WalletError::from(err)),
+        }
+    }
+
+    fn write_u32_file(&self, name: &str, value: u32) -> Result<(), WalletError> {
+        fs::create_dir_all(&self.data_dir)?;
+        let mut file = fs::File::create(self.data_dir.join(name))?;
+        write!(file, "{}", value)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        str::FromStr,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use bitcoin::{absolute::LockTime, Sequence, Transaction, TxIn, TxOut, Witness};
+
+    const TEST_XPUB: &str = "xpub661MyMwAqKaxyjo5eM3FJwCJPXZo4UWjXGDiSkkVUBjaFFMVWy1xaGc9kHuN9sKn4xkMpXcnTSBUjj2mbG7FQRwmuTGGiA9rVoRKZCkc62b";
+    static NEXT_TEST_DIR: AtomicU32 = AtomicU32::new(0);
+
+    fn test_wallet() -> Wallet {
+        let xpub = ExtendedPubKey::from_str(TEST_XPUB).expect("valid test xpub");
+        let id = NEXT_TEST_DIR.fetch_add(1, Ordering::SeqCst);
+        let data_dir = std::env::temp_dir().join(format!("coinswap-wallet-test-{}-{}", std::process::id(), id));
+        Wallet::new(xpub, Network::Regtest, data_dir).expect("wallet init")
+    }
+
+    fn txid_from_byte(byte: u8) -> Txid {
+        Txid::from_str(&format!("{:02x}", byte).repeat(32)).expect("valid txid hex")
+    }
+
+    fn spending_tx(inputs: Vec<OutPoint>) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: inputs
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    script_sig: Script::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: vec![TxOut { value: Amount::from_sat(49_000), script_pubkey: Script::new() }],
+        }
+    }
+
+    #[test]
+    fn remove_utxo_moves_it_out_of_the_live_set() {
+        let wallet = test_wallet();
+        let outpoint = OutPoint::new(txid_from_byte(0xaa), 0);
+
+        wallet.add_utxo(outpoint, Amount::from_sat(1_000), Script::new()).unwrap();
+        assert!(wallet.is_utxo_tracked(&outpoint).unwrap());
+
+        wallet.remove_utxo(&outpoint).unwrap();
+        assert!(!wallet.is_utxo_tracked(&outpoint).unwrap());
+    }
+
+    #[test]
+    fn revert_transaction_deletes_its_outputs_and_recredits_its_inputs() {
+        let wallet = test_wallet();
+        let funding_outpoint = OutPoint::new(txid_from_byte(0x11), 0);
+        wallet
+            .add_utxo(funding_outpoint, Amount::from_sat(50_000), Script::new())
+            .unwrap();
+
+        let tx = spending_tx(vec![funding_outpoint]);
+        let txid = tx.txid();
+        let created_outpoint = OutPoint::new(txid, 0);
+
+        wallet.store_transaction(tx).unwrap();
+        wallet.remove_utxo(&funding_outpoint).unwrap();
+        wallet
+            .add_utxo(created_outpoint, Amount::from_sat(49_000), Script::new())
+            .unwrap();
+
+        wallet.revert_transaction(&txid).unwrap();
+
+        assert!(!wallet.is_utxo_tracked(&created_outpoint).unwrap());
+        assert!(wallet.is_utxo_tracked(&funding_outpoint).unwrap());
+    }
+
+    #[test]
+    fn extend_derivation_for_gap_limit_tops_up_the_lookahead_window() {
+        let wallet = test_wallet();
+        let initial = wallet.derive_gap_limit_scripts(5).unwrap();
+        let used = initial[2].clone();
+
+        let fresh = wallet.extend_derivation_for_gap_limit(&used, 5).unwrap();
+
+        assert_eq!(fresh.len(), 3);
+        assert_eq!(wallet.tracked_scripts().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn extend_derivation_for_gap_limit_is_a_no_op_for_an_unknown_script() {
+        let wallet = test_wallet();
+        wallet.derive_gap_limit_scripts(5).unwrap();
+
+        let fresh = wallet.extend_derivation_for_gap_limit(&Script::new(), 5).unwrap();
+        assert!(fresh.is_empty());
+    }
+}